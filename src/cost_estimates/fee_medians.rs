@@ -1,5 +1,6 @@
 use std::cmp;
 use std::cmp::Ordering;
+use std::collections::HashMap;
 use std::convert::TryFrom;
 use std::{iter::FromIterator, path::Path};
 
@@ -9,6 +10,8 @@ use rusqlite::{
     types::{FromSql, FromSqlError},
     Connection, Error as SqliteError, OptionalExtension, ToSql,
 };
+use serde::de::Error as SerdeDeError;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
 use serde_json::Value as JsonValue;
 
 use chainstate::stacks::TransactionPayload;
@@ -18,6 +21,7 @@ use util::db::u64_to_sql;
 
 use vm::costs::ExecutionCost;
 
+use burnchains::ConsensusHash;
 use chainstate::stacks::db::StacksEpochReceipt;
 use chainstate::stacks::events::TransactionOrigin;
 
@@ -31,13 +35,103 @@ use super::{EstimatorError, FeeEstimator};
 use super::metrics::PROPORTION_RESOLUTION;
 use cost_estimates::StacksTransactionReceipt;
 
+/// Identifies the fork point passed to `notify_block_rollback`: either a height the
+/// caller already knows, or a `ConsensusHash` to look up -- the block identifier
+/// persisted with every measurement is exactly this pair, so a caller that only knows
+/// the abandoned tip's hash can still resolve it to a rollback point.
+pub enum RollbackPoint {
+    Height(u64),
+    ConsensusHash(ConsensusHash),
+}
+
 const SINGLETON_ROW_ID: i64 = 1;
 const CREATE_TABLE: &'static str = "
 CREATE TABLE median_fee_estimator (
     measure_key INTEGER PRIMARY KEY AUTOINCREMENT,
-    high NUMBER NOT NULL,
-    middle NUMBER NOT NULL,
-    low NUMBER NOT NULL
+    high INTEGER NOT NULL,
+    middle INTEGER NOT NULL,
+    low INTEGER NOT NULL,
+    block_height INTEGER NOT NULL,
+    consensus_hash TEXT NOT NULL,
+    fee_rates TEXT NOT NULL
+)";
+
+/// The furthest back a single `notify_block_rollback` call is allowed to unwind the
+/// measurement window. This keeps a malicious or buggy reorg notification from forcing
+/// the estimator to rewrite measurements far deeper than any realistic fork.
+const MAXIMUM_ROLLBACK_DEPTH: u64 = 1008;
+
+/// The lowest block height a rollback to `fork_height` is allowed to reach, given that
+/// the window currently holds measurements up to `max_observed_height`: `fork_height`,
+/// clamped so the rewind never exceeds `MAXIMUM_ROLLBACK_DEPTH` blocks.
+fn bounded_rollback_height(fork_height: u64, max_observed_height: u64) -> u64 {
+    cmp::max(
+        fork_height,
+        max_observed_height.saturating_sub(MAXIMUM_ROLLBACK_DEPTH),
+    )
+}
+
+/// The independent dimensions that a transaction can use up. `ExecutionCost` supplies
+/// the first five; `TxLen` is the transaction's contribution to the block's serialized
+/// length limit. Tracking these separately (rather than flattening them into one scalar
+/// via `CostMetric`) lets a caller see which dimension is actually binding for a block.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum CostDimension {
+    RuntimeCost,
+    ReadCount,
+    ReadLength,
+    WriteCount,
+    WriteLength,
+    TxLen,
+}
+
+pub const ALL_COST_DIMENSIONS: [CostDimension; 6] = [
+    CostDimension::RuntimeCost,
+    CostDimension::ReadCount,
+    CostDimension::ReadLength,
+    CostDimension::WriteCount,
+    CostDimension::WriteLength,
+    CostDimension::TxLen,
+];
+
+impl CostDimension {
+    fn column_name(&self) -> &'static str {
+        match self {
+            CostDimension::RuntimeCost => "runtime_cost",
+            CostDimension::ReadCount => "read_count",
+            CostDimension::ReadLength => "read_length",
+            CostDimension::WriteCount => "write_count",
+            CostDimension::WriteLength => "write_length",
+            CostDimension::TxLen => "tx_len",
+        }
+    }
+
+    fn from_column_name(name: &str) -> CostDimension {
+        match name {
+            "runtime_cost" => CostDimension::RuntimeCost,
+            "read_count" => CostDimension::ReadCount,
+            "read_length" => CostDimension::ReadLength,
+            "write_count" => CostDimension::WriteCount,
+            "write_length" => CostDimension::WriteLength,
+            "tx_len" => CostDimension::TxLen,
+            _ => panic!("Unknown cost dimension column name: {}", name),
+        }
+    }
+}
+
+/// The default assumed limit on the total serialized length of transactions in a
+/// block, used as the denominator for the `CostDimension::TxLen` proportion.
+const MAX_BLOCK_LENGTH: u64 = 2 * 1024 * 1024;
+
+const DIMENSIONAL_CREATE_TABLE: &'static str = "
+CREATE TABLE dimensional_fee_estimator (
+    measure_key INTEGER PRIMARY KEY AUTOINCREMENT,
+    dimension TEXT NOT NULL,
+    high INTEGER NOT NULL,
+    middle INTEGER NOT NULL,
+    low INTEGER NOT NULL,
+    block_height INTEGER NOT NULL,
+    is_binding INTEGER NOT NULL
 )";
 
 /// This struct estimates fee rates by translating a transaction's `ExecutionCost`
@@ -55,13 +149,111 @@ pub struct WeightedMedianFeeRateEstimator<M: CostMetric> {
     /// The weight of a "full block" in abstract scalar cost units. This is the weight of
     /// a block that is filled on each dimension.
     full_block_weight: u64,
+    /// The maximum serialized length, in bytes, of transactions in a block. Used as the
+    /// limit against which the `CostDimension::TxLen` dimension's proportion is computed.
+    block_length_limit: u64,
+    /// Set by `set_in_initial_block_download` while the node is bootstrapping or
+    /// replaying history, rather than processing blocks live. While set, `notify_block`
+    /// does not persist measurements, so a sync or replay does not pollute the window
+    /// with stale fees.
+    catching_up: bool,
     metric: M,
 }
 
+/// Number of fractional bits used by `FixedPoint`. Chosen to give ample precision for
+/// per-unit fee rates while leaving headroom in `i128` for the multiply-divide used
+/// during weighted-percentile interpolation.
+const FIXED_POINT_FRACTIONAL_BITS: u32 = 32;
+const FIXED_POINT_ONE: i128 = 1i128 << FIXED_POINT_FRACTIONAL_BITS;
+
+/// A fixed-point fee rate: a signed 128-bit integer with `FIXED_POINT_FRACTIONAL_BITS`
+/// fractional bits, used in place of `f64` for fee rate math. Unlike `f64`, comparisons
+/// are a total order (there is no NaN), and the multiply-divide helpers below saturate
+/// on overflow instead of producing NaN/infinity, so results are bit-reproducible across
+/// platforms.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+struct FixedPoint(i128);
+
+impl FixedPoint {
+    const ONE: FixedPoint = FixedPoint(FIXED_POINT_ONE);
+
+    /// `(numerator << FIXED_POINT_FRACTIONAL_BITS) / denominator`, saturating instead
+    /// of dividing by zero or overflowing.
+    fn from_ratio(numerator: u64, denominator: u64) -> FixedPoint {
+        Self::from_ratio_i128(numerator as i128, denominator as i128)
+    }
+
+    fn from_ratio_i128(numerator: i128, denominator: i128) -> FixedPoint {
+        if denominator == 0 {
+            return FixedPoint(i128::MAX);
+        }
+        let scaled = numerator.saturating_mul(FIXED_POINT_ONE);
+        FixedPoint(scaled / denominator)
+    }
+
+    fn checked_mul(self, other: FixedPoint) -> FixedPoint {
+        FixedPoint(self.0.saturating_mul(other.0) / FIXED_POINT_ONE)
+    }
+
+    fn checked_div(self, other: FixedPoint) -> FixedPoint {
+        Self::from_ratio_i128(self.0, other.0)
+    }
+
+    fn to_f64(self) -> f64 {
+        self.0 as f64 / FIXED_POINT_ONE as f64
+    }
+
+    fn from_f64(n: f64) -> FixedPoint {
+        FixedPoint((n * FIXED_POINT_ONE as f64) as i128)
+    }
+
+    /// Narrow the raw fixed-point value (fractional bits included) to a signed 64-bit
+    /// integer for SQLite storage, saturating rather than overflowing if the value is
+    /// out of `i64` range.
+    fn to_storage(self) -> i64 {
+        cmp::min(cmp::max(self.0, i64::MIN as i128), i64::MAX as i128) as i64
+    }
+
+    fn from_storage(n: i64) -> FixedPoint {
+        FixedPoint(n as i128)
+    }
+}
+
+impl std::ops::Add for FixedPoint {
+    type Output = FixedPoint;
+    fn add(self, other: FixedPoint) -> FixedPoint {
+        FixedPoint(self.0.saturating_add(other.0))
+    }
+}
+
+impl std::ops::Sub for FixedPoint {
+    type Output = FixedPoint;
+    fn sub(self, other: FixedPoint) -> FixedPoint {
+        FixedPoint(self.0.saturating_sub(other.0))
+    }
+}
+
+/// Fixed-point values serialize as decimal strings of the underlying `i128`, rather
+/// than relying on serde_json's (feature-gated) i128 support.
+impl Serialize for FixedPoint {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.0.to_string())
+    }
+}
+
+impl<'de> Deserialize<'de> for FixedPoint {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let raw = String::deserialize(deserializer)?;
+        raw.parse::<i128>()
+            .map(FixedPoint)
+            .map_err(SerdeDeError::custom)
+    }
+}
+
 /// Convenience pair for return values.
-#[derive(Debug)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 struct FeeRateAndWeight {
-    pub fee_rate: f64,
+    pub fee_rate: FixedPoint,
     pub weight: u64,
 }
 
@@ -86,20 +278,81 @@ impl<M: CostMetric> WeightedMedianFeeRateEstimator<M> {
             metric,
             window_size,
             full_block_weight: 6 * PROPORTION_RESOLUTION,
+            block_length_limit: MAX_BLOCK_LENGTH,
+            catching_up: false,
         })
     }
 
+    /// Tell the estimator whether the node is currently in initial block download or
+    /// replaying history. While `catching_up` is `true`, `notify_block` skips persisting
+    /// measurements entirely, so that the window reflects current network conditions as
+    /// soon as the node catches up, rather than being full of historical fees.
+    pub fn set_in_initial_block_download(&mut self, catching_up: bool) {
+        self.catching_up = catching_up;
+    }
+
     /// Check if the SQL database was already created. Necessary to avoid races if
     ///  different threads open an estimator at the same time.
     fn db_already_instantiated(tx: &SqlTransaction) -> Result<bool, SqliteError> {
         table_exists(tx, "median_fee_estimator")
     }
 
+    fn dimensional_db_already_instantiated(tx: &SqlTransaction) -> Result<bool, SqliteError> {
+        table_exists(tx, "dimensional_fee_estimator")
+    }
+
+    /// Both estimator tables are rebuildable caches (a fresh window just means a few
+    /// blocks of less-informed estimates, not a correctness issue), so rather than carry
+    /// forward an `ALTER TABLE` migration for every schema change in this series, a table
+    /// left over from an older schema is simply dropped and recreated. Without this, a
+    /// node upgrading from a pre-series database would keep its old, narrower
+    /// `median_fee_estimator`/`dimensional_fee_estimator` table, and the first `INSERT`
+    /// against the new schema's columns would panic in the block-processing path.
+    fn table_matches_current_schema(
+        tx: &SqlTransaction,
+        table: &str,
+        expected_columns: &[&str],
+    ) -> Result<bool, SqliteError> {
+        let sql = format!("PRAGMA table_info({})", table);
+        let mut stmt = tx.prepare(&sql)?;
+        let actual_columns: Vec<String> = stmt
+            .query_and_then::<_, SqliteError, _, _>(rusqlite::NO_PARAMS, |row| row.get(1))?
+            .collect::<Result<_, SqliteError>>()?;
+
+        Ok(expected_columns
+            .iter()
+            .all(|column| actual_columns.iter().any(|actual| actual == column)))
+    }
+
     fn instantiate_db(tx: &SqlTransaction) -> Result<(), SqliteError> {
+        if Self::db_already_instantiated(tx)?
+            && !Self::table_matches_current_schema(
+                tx,
+                "median_fee_estimator",
+                &["high", "middle", "low", "block_height", "consensus_hash", "fee_rates"],
+            )?
+        {
+            tx.execute("DROP TABLE median_fee_estimator", rusqlite::NO_PARAMS)?;
+        }
+
         if !Self::db_already_instantiated(tx)? {
             tx.execute(CREATE_TABLE, rusqlite::NO_PARAMS)?;
         }
 
+        if Self::dimensional_db_already_instantiated(tx)?
+            && !Self::table_matches_current_schema(
+                tx,
+                "dimensional_fee_estimator",
+                &["dimension", "high", "middle", "low", "block_height", "is_binding"],
+            )?
+        {
+            tx.execute("DROP TABLE dimensional_fee_estimator", rusqlite::NO_PARAMS)?;
+        }
+
+        if !Self::dimensional_db_already_instantiated(tx)? {
+            tx.execute(DIMENSIONAL_CREATE_TABLE, rusqlite::NO_PARAMS)?;
+        }
+
         Ok(())
     }
 
@@ -117,10 +370,14 @@ impl<M: CostMetric> WeightedMedianFeeRateEstimator<M> {
         let mut lows = Vec::with_capacity(window_size as usize);
         let results = stmt
             .query_and_then::<_, SqliteError, _, _>(&[window_size], |row| {
-                let high: f64 = row.get(0)?;
-                let middle: f64 = row.get(1)?;
-                let low: f64 = row.get(2)?;
-                Ok((low, middle, high))
+                let high: i64 = row.get(0)?;
+                let middle: i64 = row.get(1)?;
+                let low: i64 = row.get(2)?;
+                Ok((
+                    FixedPoint::from_storage(low),
+                    FixedPoint::from_storage(middle),
+                    FixedPoint::from_storage(high),
+                ))
             })
             .expect("SQLite failure");
 
@@ -135,43 +392,59 @@ impl<M: CostMetric> WeightedMedianFeeRateEstimator<M> {
             return Err(EstimatorError::NoEstimateAvailable);
         }
 
-        fn median(len: usize, l: Vec<f64>) -> f64 {
+        // `FixedPoint` is a total order, so there is no need to guard against
+        //  incomparable (NaN) values the way `f64::partial_cmp` would require.
+        fn median(len: usize, l: Vec<FixedPoint>) -> FixedPoint {
             if len % 2 == 1 {
                 l[len / 2]
             } else {
                 // note, measures_len / 2 - 1 >= 0, because
                 //  len % 2 == 0 and emptiness is checked above
-                (l[len / 2] + l[len / 2 - 1]) / 2f64
+                FixedPoint((l[len / 2] + l[len / 2 - 1]).0 / 2)
             }
         }
 
-        // sort our float arrays. for float values that do not compare easily,
-        //  treat them as equals.
-        highs.sort_by(|a, b| a.partial_cmp(b).unwrap_or(Ordering::Equal));
-        mids.sort_by(|a, b| a.partial_cmp(b).unwrap_or(Ordering::Equal));
-        lows.sort_by(|a, b| a.partial_cmp(b).unwrap_or(Ordering::Equal));
+        highs.sort();
+        mids.sort();
+        lows.sort();
 
         Ok(FeeRateEstimate {
-            high: median(highs.len(), highs),
-            middle: median(mids.len(), mids),
-            low: median(lows.len(), lows),
+            high: median(highs.len(), highs).to_f64(),
+            middle: median(mids.len(), mids).to_f64(),
+            low: median(lows.len(), lows).to_f64(),
         })
     }
 
-    fn update_estimate(&mut self, new_measure: FeeRateEstimate) {
+    fn update_estimate(
+        &mut self,
+        new_measure: FeeRateEstimate,
+        sorted_fee_rates: &[FeeRateAndWeight],
+        block_height: u64,
+        consensus_hash: &ConsensusHash,
+    ) {
         let tx = tx_begin_immediate_sqlite(&mut self.db).expect("SQLite failure");
 
         let insert_sql = "INSERT INTO median_fee_estimator
-                          (high, middle, low) VALUES (?, ?, ?)";
+                          (high, middle, low, block_height, consensus_hash, fee_rates) VALUES (?, ?, ?, ?, ?, ?)";
 
         let deletion_sql = "DELETE FROM median_fee_estimator
                             WHERE measure_key <= (
                                SELECT MAX(measure_key) - ?
                                FROM median_fee_estimator )";
 
+        let fee_rates_json =
+            serde_json::to_string(sorted_fee_rates).expect("Failed to serialize fee rates");
+
         tx.execute(
             insert_sql,
-            rusqlite::params![new_measure.high, new_measure.middle, new_measure.low,],
+            rusqlite::params![
+                FixedPoint::from_f64(new_measure.high).to_storage(),
+                FixedPoint::from_f64(new_measure.middle).to_storage(),
+                FixedPoint::from_f64(new_measure.low).to_storage(),
+                u64_to_sql(block_height).expect("Block height too large"),
+                consensus_hash.to_hex(),
+                fee_rates_json,
+            ],
         )
         .expect("SQLite failure");
 
@@ -192,6 +465,291 @@ impl<M: CostMetric> WeightedMedianFeeRateEstimator<M> {
                    "new_estimate_low" => next_estimate.low);
         }
     }
+
+    /// Unwind all measurements taken at or above `fork_point`, so that blocks
+    /// which were orphaned by a reorg do not continue to influence the fee window
+    /// once the replacement blocks are notified. The amount of history this can
+    /// rewind is bounded by `MAXIMUM_ROLLBACK_DEPTH`, so a bogus fork point cannot
+    /// force the whole window to be rewritten.
+    pub fn notify_block_rollback(&mut self, fork_point: RollbackPoint) -> Result<(), EstimatorError> {
+        let tx = tx_begin_immediate_sqlite(&mut self.db).expect("SQLite failure");
+
+        let fork_height = match fork_point {
+            RollbackPoint::Height(height) => Some(height),
+            RollbackPoint::ConsensusHash(ref consensus_hash) => tx
+                .query_row(
+                    "SELECT block_height FROM median_fee_estimator WHERE consensus_hash = ?",
+                    rusqlite::params![consensus_hash.to_hex()],
+                    |row| row.get::<_, i64>(0),
+                )
+                .optional()
+                .expect("SQLite failure")
+                .map(|height| height as u64),
+        };
+
+        // The caller identified a fork point we have no record of (e.g. a hash from
+        //  before this window's history, or a race with a concurrent rollback);
+        //  there is nothing to unwind.
+        let fork_height = match fork_height {
+            Some(height) => height,
+            None => return Ok(()),
+        };
+
+        let max_height: Option<i64> = tx
+            .query_row(
+                "SELECT MAX(block_height) FROM median_fee_estimator",
+                rusqlite::NO_PARAMS,
+                |row| row.get(0),
+            )
+            .expect("SQLite failure");
+
+        let bounded_fork_height = match max_height {
+            Some(max_height) => bounded_rollback_height(fork_height, max_height as u64),
+            None => return Ok(()),
+        };
+
+        tx.execute(
+            "DELETE FROM median_fee_estimator WHERE block_height >= ?",
+            rusqlite::params![u64_to_sql(bounded_fork_height).expect("Block height too large")],
+        )
+        .expect("SQLite failure");
+
+        // The dimensional window is populated in lockstep with the scalar window on
+        //  every `notify_block`, so it must be rolled back to the same fork point --
+        //  otherwise orphaned-block measurements would persist forever per-dimension.
+        tx.execute(
+            "DELETE FROM dimensional_fee_estimator WHERE block_height >= ?",
+            rusqlite::params![u64_to_sql(bounded_fork_height).expect("Block height too large")],
+        )
+        .expect("SQLite failure");
+
+        tx.commit().expect("SQLite failure");
+
+        debug!("Rolled back fee rate measurements for reorg";
+               "requested_fork_height" => fork_height,
+               "bounded_fork_height" => bounded_fork_height);
+
+        Ok(())
+    }
+
+    /// For each `CostDimension`, compute this block's weighted fee rate estimate and
+    /// persist it into the dimensional window, independently of the collapsed-scalar
+    /// window used by `update_estimate`. `binding_dimension`, as chosen by the caller,
+    /// is recorded as the block's binding dimension; it is not recomputed from the
+    /// (already padded) weights in `block_dimensions`.
+    fn update_dimensional_estimates(
+        &mut self,
+        block_height: u64,
+        binding_dimension: Option<CostDimension>,
+        block_dimensions: Vec<(CostDimension, Vec<FeeRateAndWeight>)>,
+    ) {
+        let tx = tx_begin_immediate_sqlite(&mut self.db).expect("SQLite failure");
+
+        let insert_sql = "INSERT INTO dimensional_fee_estimator
+                          (dimension, high, middle, low, block_height, is_binding) VALUES (?, ?, ?, ?, ?, ?)";
+        let deletion_sql = "DELETE FROM dimensional_fee_estimator
+                            WHERE dimension = ?1 AND measure_key <= (
+                               SELECT MAX(measure_key) - ?2
+                               FROM dimensional_fee_estimator
+                               WHERE dimension = ?1 )";
+
+        for (dimension, mut rates) in block_dimensions {
+            if rates.is_empty() {
+                continue;
+            }
+            rates.sort_by_key(|rate_and_weight| rate_and_weight.fee_rate);
+            let estimate = fee_rate_estimate_from_sorted_weighted_fees(&rates);
+            let is_binding = Some(dimension) == binding_dimension;
+
+            tx.execute(
+                insert_sql,
+                rusqlite::params![
+                    dimension.column_name(),
+                    FixedPoint::from_f64(estimate.high).to_storage(),
+                    FixedPoint::from_f64(estimate.middle).to_storage(),
+                    FixedPoint::from_f64(estimate.low).to_storage(),
+                    u64_to_sql(block_height).expect("Block height too large"),
+                    is_binding,
+                ],
+            )
+            .expect("SQLite failure");
+
+            tx.execute(
+                deletion_sql,
+                rusqlite::params![dimension.column_name(), self.window_size],
+            )
+            .expect("SQLite failure");
+        }
+
+        tx.commit().expect("SQLite failure");
+    }
+
+    /// Return the current median `FeeRateEstimate` for each `CostDimension`, independent
+    /// of the collapsed-scalar estimate returned by `get_rate_estimates`.
+    pub fn get_dimensional_rate_estimates(
+        &self,
+    ) -> Result<HashMap<CostDimension, FeeRateEstimate>, EstimatorError> {
+        let mut estimates = HashMap::new();
+        for dimension in ALL_COST_DIMENSIONS.iter() {
+            let sql = "SELECT high, middle, low FROM dimensional_fee_estimator
+                       WHERE dimension = ? ORDER BY measure_key DESC LIMIT ?";
+            let mut stmt = self.db.prepare(sql).expect("SQLite failure");
+
+            let mut highs = Vec::new();
+            let mut mids = Vec::new();
+            let mut lows = Vec::new();
+            let results = stmt
+                .query_and_then::<_, SqliteError, _, _>(
+                    rusqlite::params![dimension.column_name(), self.window_size],
+                    |row| {
+                        let high: i64 = row.get(0)?;
+                        let middle: i64 = row.get(1)?;
+                        let low: i64 = row.get(2)?;
+                        Ok((
+                            FixedPoint::from_storage(low),
+                            FixedPoint::from_storage(middle),
+                            FixedPoint::from_storage(high),
+                        ))
+                    },
+                )
+                .expect("SQLite failure");
+
+            for result in results {
+                let (low, middle, high) = result.expect("SQLite failure");
+                highs.push(high);
+                mids.push(middle);
+                lows.push(low);
+            }
+
+            if highs.is_empty() {
+                continue;
+            }
+
+            highs.sort();
+            mids.sort();
+            lows.sort();
+
+            fn median(len: usize, l: Vec<FixedPoint>) -> FixedPoint {
+                if len % 2 == 1 {
+                    l[len / 2]
+                } else {
+                    FixedPoint((l[len / 2] + l[len / 2 - 1]).0 / 2)
+                }
+            }
+
+            estimates.insert(
+                *dimension,
+                FeeRateEstimate {
+                    high: median(highs.len(), highs).to_f64(),
+                    middle: median(mids.len(), mids).to_f64(),
+                    low: median(lows.len(), lows).to_f64(),
+                },
+            );
+        }
+
+        if estimates.is_empty() {
+            return Err(EstimatorError::NoEstimateAvailable);
+        }
+
+        Ok(estimates)
+    }
+
+    /// Return the dimension that was binding (fullest relative to its share of the
+    /// block limit) in the most recently notified block, if known.
+    pub fn get_binding_dimension(&self) -> Result<CostDimension, EstimatorError> {
+        let sql = "SELECT dimension FROM dimensional_fee_estimator
+                   WHERE is_binding = 1 ORDER BY measure_key DESC LIMIT 1";
+        self.db
+            .query_row(sql, rusqlite::NO_PARAMS, |row| {
+                let dimension: String = row.get(0)?;
+                Ok(CostDimension::from_column_name(&dimension))
+            })
+            .optional()
+            .expect("SQLite failure")
+            .ok_or(EstimatorError::NoEstimateAvailable)
+    }
+
+    /// Fetch the per-block sorted fee rate distributions for up to the last
+    /// `window_size` blocks, most recent first.
+    fn get_fee_rate_window(&self) -> Vec<Vec<FeeRateAndWeight>> {
+        let sql = "SELECT fee_rates FROM median_fee_estimator ORDER BY measure_key DESC LIMIT ?";
+        let mut stmt = self.db.prepare(sql).expect("SQLite failure");
+        let results = stmt
+            .query_and_then::<_, SqliteError, _, _>(&[self.window_size], |row| {
+                let fee_rates_json: String = row.get(0)?;
+                Ok(fee_rates_json)
+            })
+            .expect("SQLite failure");
+
+        results
+            .map(|result| {
+                let fee_rates_json = result.expect("SQLite failure");
+                serde_json::from_str(&fee_rates_json).expect("Failed to deserialize fee rates")
+            })
+            .collect()
+    }
+
+    /// The fraction of `block`'s weight that would have been beaten by a transaction
+    /// paying `fee_rate`, i.e. the empirical probability that such a transaction would
+    /// have been included in this block.
+    fn inclusion_probability_in_block(block: &[FeeRateAndWeight], fee_rate: FixedPoint) -> f64 {
+        let total_weight: u64 = block.iter().map(|r| r.weight).sum();
+        if total_weight == 0 {
+            return 1f64;
+        }
+        let weight_at_or_below: u64 = block
+            .iter()
+            .filter(|r| r.fee_rate <= fee_rate)
+            .map(|r| r.weight)
+            .sum();
+
+        weight_at_or_below as f64 / total_weight as f64
+    }
+
+    /// Map a desired number of blocks to confirmation to a fee rate, in the spirit of
+    /// Bitcoin Core's `estimatefee nblocks` / smartfee: retain the full sorted fee rate
+    /// distribution for each block in the window, and return the lowest fee rate whose
+    /// empirical inclusion probability over the window is at least `1 / blocks`. A
+    /// `blocks` of 1 approaches the high percentile; a large `blocks` approaches the low
+    /// percentile.
+    pub fn estimate_fee_for_target(&self, blocks: u16) -> Result<f64, EstimatorError> {
+        if blocks == 0 {
+            return Err(EstimatorError::NoEstimateAvailable);
+        }
+
+        let window = self.get_fee_rate_window();
+        if window.len() < blocks as usize {
+            return Err(EstimatorError::NoEstimateAvailable);
+        }
+
+        let target_probability = 1f64 / blocks as f64;
+
+        let mut candidate_rates: Vec<FixedPoint> = window
+            .iter()
+            .flat_map(|block| block.iter().map(|r| r.fee_rate))
+            .collect();
+        candidate_rates.sort();
+        candidate_rates.dedup();
+
+        for &candidate in candidate_rates.iter() {
+            let mean_inclusion_probability = window
+                .iter()
+                .map(|block| Self::inclusion_probability_in_block(block, candidate))
+                .sum::<f64>()
+                / window.len() as f64;
+
+            if mean_inclusion_probability >= target_probability {
+                return Ok(candidate.to_f64());
+            }
+        }
+
+        // No observed rate reached the target probability; the highest rate seen is
+        //  our best estimate.
+        candidate_rates
+            .last()
+            .map(|rate| rate.to_f64())
+            .ok_or(EstimatorError::NoEstimateAvailable)
+    }
 }
 
 impl<M: CostMetric> FeeEstimator for WeightedMedianFeeRateEstimator<M> {
@@ -202,6 +760,12 @@ impl<M: CostMetric> FeeEstimator for WeightedMedianFeeRateEstimator<M> {
         receipt: &StacksEpochReceipt,
         block_limit: &ExecutionCost,
     ) -> Result<(), EstimatorError> {
+        if self.catching_up {
+            // Bootstrapping or replaying history: don't let these measurements pollute
+            //  the window with stale fees.
+            return Ok(());
+        }
+
         // Calculate sorted fee rate for each transaction in the block.
         let mut working_fee_rates: Vec<FeeRateAndWeight> = receipt
             .tx_receipts
@@ -216,16 +780,63 @@ impl<M: CostMetric> FeeEstimator for WeightedMedianFeeRateEstimator<M> {
 
         // If fee rates non-empty, then compute an update.
         if working_fee_rates.len() > 0 {
-            working_fee_rates.sort_by(|a, b| {
-                a.fee_rate
-                    .partial_cmp(&b.fee_rate)
-                    .unwrap_or(Ordering::Equal)
-            });
+            working_fee_rates.sort_by_key(|rate_and_weight| rate_and_weight.fee_rate);
 
             let block_estimate = fee_rate_estimate_from_sorted_weighted_fees(&working_fee_rates);
-            self.update_estimate(block_estimate);
+            self.update_estimate(
+                block_estimate,
+                &working_fee_rates,
+                receipt.header.block_height,
+                &receipt.header.consensus_hash,
+            );
+        }
+
+        // Track each cost dimension's fee rate independently, so callers can tell
+        //  which dimension is actually binding for a given workload.
+        let mut per_dimension: HashMap<CostDimension, Vec<FeeRateAndWeight>> = HashMap::new();
+        for tx_receipt in receipt.tx_receipts.iter() {
+            if let Some(dimensional_rates) = fee_rate_and_weight_per_dimension(
+                &tx_receipt,
+                block_limit,
+                self.block_length_limit,
+            ) {
+                for (dimension, rate_and_weight) in dimensional_rates {
+                    per_dimension
+                        .entry(dimension)
+                        .or_insert_with(Vec::new)
+                        .push(rate_and_weight);
+                }
+            }
+        }
+
+        // Determine the binding dimension from the *raw* (pre-padding) weights: once
+        //  `maybe_add_minimum_fee_rate` tops every non-overfull dimension up to
+        //  `PROPORTION_RESOLUTION`, a block near full capacity on its true binding
+        //  dimension would tie every dimension's summed weight, making the padded
+        //  weights useless for telling dimensions apart.
+        let binding_dimension = ALL_COST_DIMENSIONS
+            .iter()
+            .max_by_key(|dimension| {
+                per_dimension
+                    .get(*dimension)
+                    .map(|rates| rates.iter().map(|r| r.weight).sum::<u64>())
+                    .unwrap_or(0)
+            })
+            .copied();
+
+        let mut block_dimensions: Vec<(CostDimension, Vec<FeeRateAndWeight>)> = Vec::new();
+        for dimension in ALL_COST_DIMENSIONS.iter() {
+            let mut rates = per_dimension.remove(dimension).unwrap_or_else(Vec::new);
+            maybe_add_minimum_fee_rate(&mut rates, PROPORTION_RESOLUTION);
+            block_dimensions.push((*dimension, rates));
         }
 
+        self.update_dimensional_estimates(
+            receipt.header.block_height,
+            binding_dimension,
+            block_dimensions,
+        );
+
         Ok(())
     }
 
@@ -250,43 +861,236 @@ fn fee_rate_estimate_from_sorted_weighted_fees(
     let mut percentiles = Vec::new();
     for rate_and_weight in sorted_fee_rates {
         cumulative_weight += rate_and_weight.weight;
-        let percentile_n: f64 =
-            (cumulative_weight as f64 - rate_and_weight.weight as f64 / 2f64) / total_weight as f64;
-        percentiles.push(percentile_n);
+        // percentile_n = (cumulative_weight - weight/2) / total_weight, computed as
+        //  (2*cumulative_weight - weight) / (2*total_weight) to avoid a fractional
+        //  intermediate value.
+        let numerator = 2 * cumulative_weight as i128 - rate_and_weight.weight as i128;
+        let denominator = 2 * total_weight as i128;
+        percentiles.push(FixedPoint::from_ratio_i128(numerator, denominator));
     }
 
-    let target_percentiles = vec![0.05, 0.5, 0.95];
+    let target_percentiles = vec![
+        FixedPoint::from_ratio(5, 100),
+        FixedPoint::from_ratio(1, 2),
+        FixedPoint::from_ratio(95, 100),
+    ];
     let mut fees_index = 0; // index into `sorted_fee_rates`
     let mut values_at_target_percentiles = Vec::new();
-    warn!("percentiles {:?}", &percentiles);
-    warn!("sorted_fee_rates {:?}", &sorted_fee_rates);
-    warn!("percentiles {:?}", &percentiles);
     for target_percentile in target_percentiles {
         while fees_index < percentiles.len() && percentiles[fees_index] < target_percentile {
             fees_index += 1;
         }
         let v = if fees_index == 0 {
-            warn!("fees_index == 0");
             sorted_fee_rates[0].fee_rate
         } else if fees_index == percentiles.len() {
-            warn!("fees_index == percentiles.len()");
             sorted_fee_rates.last().unwrap().fee_rate
         } else {
-            warn!("fees_index < percentiles.len()");
             // Notation mimics https://en.wikipedia.org/wiki/Percentile#Weighted_percentile
             let vk = sorted_fee_rates[fees_index - 1].fee_rate;
             let vk1 = sorted_fee_rates[fees_index].fee_rate;
             let pk = percentiles[fees_index - 1];
             let pk1 = percentiles[fees_index];
-            vk + (target_percentile - pk) / (pk1 - pk) * (vk1 - vk)
+            vk + (target_percentile - pk).checked_div(pk1 - pk).checked_mul(vk1 - vk)
         };
         values_at_target_percentiles.push(v);
     }
 
     FeeRateEstimate {
-        high: values_at_target_percentiles[2],
-        middle: values_at_target_percentiles[1],
-        low: values_at_target_percentiles[0],
+        high: values_at_target_percentiles[2].to_f64(),
+        middle: values_at_target_percentiles[1].to_f64(),
+        low: values_at_target_percentiles[0].to_f64(),
+    }
+}
+
+const SCALAR_SINGLETON_ROW_ID: i64 = 1;
+const SCALAR_CREATE_TABLE: &'static str = "
+CREATE TABLE scalar_fee_estimator (
+    singleton_row_id INTEGER PRIMARY KEY,
+    multiplier NUMBER NOT NULL
+)";
+
+/// The target block occupancy that the multiplier adjustment tries to hold the chain at.
+/// Below this, the multiplier relaxes back down; above it, the multiplier grows.
+const TARGET_BLOCK_FULLNESS: f64 = 0.25;
+/// Adjustment variable `v`: how strongly a single block's deviation from
+/// `TARGET_BLOCK_FULLNESS` moves the multiplier. Kept small so that the multiplier
+/// reacts to sustained congestion rather than single-block noise.
+const MULTIPLIER_ADJUSTMENT_VARIABLE: f64 = 0.00001;
+/// The multiplier is never allowed to relax below this floor.
+const MIN_MULTIPLIER: f64 = 1.0;
+
+/// This struct estimates fee rates using a single, continuously adjusted fee
+/// multiplier `m`, in the style of Substrate's `TargetedFeeAdjustment`. Rather than
+/// computing percentiles over a window of past blocks, `m` is nudged up or down on
+/// every block based on how full the block was relative to `TARGET_BLOCK_FULLNESS`.
+/// This produces a smooth, momentum-based fee market: sustained congestion ratchets
+/// `m` up over many blocks, and quiet periods let it relax back down towards
+/// `MIN_MULTIPLIER`.
+pub struct ScalarFeeRateEstimator<M: CostMetric> {
+    db: Connection,
+    /// The weight of a "full block" in abstract scalar cost units. This is the weight of
+    /// a block that is filled on each dimension.
+    full_block_weight: u64,
+    /// The fee rate used as the basis for the low/middle/high bands, scaled by `m`.
+    base_rate: f64,
+    /// Set by `set_in_initial_block_download` while the node is bootstrapping or
+    /// replaying history. While `catching_up` is `true`, `notify_block` skips updating
+    /// the persisted multiplier.
+    catching_up: bool,
+    metric: M,
+}
+
+impl<M: CostMetric> ScalarFeeRateEstimator<M> {
+    /// Open a scalar fee rate estimator at the given db path. Creates if not existent.
+    pub fn open(p: &Path, metric: M, base_rate: f64) -> Result<Self, SqliteError> {
+        let mut db = sqlite_open(
+            p,
+            rusqlite::OpenFlags::SQLITE_OPEN_CREATE | rusqlite::OpenFlags::SQLITE_OPEN_READ_WRITE,
+            false,
+        )?;
+
+        // check if the db needs to be instantiated regardless of whether or not
+        //  it was newly created: the db itself may be shared with other fee estimators,
+        //  which would not have created the necessary table for this estimator.
+        let tx = tx_begin_immediate_sqlite(&mut db)?;
+        Self::instantiate_db(&tx)?;
+        tx.commit()?;
+
+        Ok(Self {
+            db,
+            metric,
+            base_rate,
+            full_block_weight: 6 * PROPORTION_RESOLUTION,
+            catching_up: false,
+        })
+    }
+
+    /// Tell this estimator whether the node is currently bootstrapping or
+    /// replaying history. While `catching_up` is `true`, `notify_block` skips
+    /// updating the persisted multiplier.
+    pub fn set_in_initial_block_download(&mut self, catching_up: bool) {
+        self.catching_up = catching_up;
+    }
+
+    /// Check if the SQL database was already created. Necessary to avoid races if
+    ///  different threads open an estimator at the same time.
+    fn db_already_instantiated(tx: &SqlTransaction) -> Result<bool, SqliteError> {
+        table_exists(tx, "scalar_fee_estimator")
+    }
+
+    fn instantiate_db(tx: &SqlTransaction) -> Result<(), SqliteError> {
+        if !Self::db_already_instantiated(tx)? {
+            tx.execute(SCALAR_CREATE_TABLE, rusqlite::NO_PARAMS)?;
+            tx.execute(
+                "INSERT INTO scalar_fee_estimator (singleton_row_id, multiplier) VALUES (?, ?)",
+                rusqlite::params![SCALAR_SINGLETON_ROW_ID, MIN_MULTIPLIER],
+            )?;
+        }
+
+        Ok(())
+    }
+
+    fn get_multiplier(conn: &Connection) -> Result<f64, SqliteError> {
+        conn.query_row(
+            "SELECT multiplier FROM scalar_fee_estimator WHERE singleton_row_id = ?",
+            rusqlite::params![SCALAR_SINGLETON_ROW_ID],
+            |row| row.get(0),
+        )
+    }
+
+    /// Given the current multiplier and this block's occupancy `s`, compute the next
+    /// multiplier using the targeted adjustment formula, clamped to `MIN_MULTIPLIER`.
+    fn next_multiplier(current: f64, occupancy: f64) -> f64 {
+        let diff = occupancy - TARGET_BLOCK_FULLNESS;
+        let v_diff = MULTIPLIER_ADJUSTMENT_VARIABLE * diff;
+        // `v_diff` already carries the sign of `diff`, so the same formula grows `m`
+        //  above target occupancy and shrinks it below -- no separate branch needed.
+        let next = current * (1f64 + v_diff + (v_diff * v_diff) / 2f64);
+
+        if next < MIN_MULTIPLIER {
+            MIN_MULTIPLIER
+        } else {
+            next
+        }
+    }
+
+    fn update_multiplier(&mut self, occupancy: f64) {
+        let tx = tx_begin_immediate_sqlite(&mut self.db).expect("SQLite failure");
+
+        let current_multiplier = Self::get_multiplier(&tx).expect("SQLite failure");
+        let next_multiplier = Self::next_multiplier(current_multiplier, occupancy);
+
+        tx.execute(
+            "UPDATE scalar_fee_estimator SET multiplier = ? WHERE singleton_row_id = ?",
+            rusqlite::params![next_multiplier, SCALAR_SINGLETON_ROW_ID],
+        )
+        .expect("SQLite failure");
+
+        tx.commit().expect("SQLite failure");
+
+        debug!("Updating scalar fee multiplier for new block";
+               "block_occupancy" => occupancy,
+               "prior_multiplier" => current_multiplier,
+               "next_multiplier" => next_multiplier);
+    }
+
+    /// Map a desired number of blocks to confirmation to a fee rate. This estimator has
+    /// no per-block distribution to draw an empirical inclusion probability from, so it
+    /// linearly interpolates between the `low` and `high` bands of `get_rate_estimates`
+    /// using `target_probability = 1 / blocks`, matching the direction used by
+    /// `WeightedMedianFeeRateEstimator::estimate_fee_for_target` (a `blocks` of 1
+    /// approaches `high`; a large `blocks` approaches `low`).
+    pub fn estimate_fee_for_target(&self, blocks: u16) -> Result<f64, EstimatorError> {
+        if blocks == 0 {
+            return Err(EstimatorError::NoEstimateAvailable);
+        }
+
+        let estimate = self.get_rate_estimates()?;
+        let target_probability = 1f64 / blocks as f64;
+
+        Ok(estimate.low + (estimate.high - estimate.low) * target_probability)
+    }
+}
+
+impl<M: CostMetric> FeeEstimator for ScalarFeeRateEstimator<M> {
+    /// Compute this block's scalar occupancy and nudge the persisted multiplier
+    /// towards or away from `TARGET_BLOCK_FULLNESS`.
+    fn notify_block(
+        &mut self,
+        receipt: &StacksEpochReceipt,
+        block_limit: &ExecutionCost,
+    ) -> Result<(), EstimatorError> {
+        if self.catching_up {
+            // Bootstrapping or replaying history: don't let these measurements pollute
+            //  the persisted multiplier.
+            return Ok(());
+        }
+
+        let total_weight: u64 = receipt
+            .tx_receipts
+            .iter()
+            .filter_map(|tx_receipt| {
+                fee_rate_and_weight_from_receipt(&self.metric, &tx_receipt, block_limit)
+            })
+            .map(|rate_and_weight| rate_and_weight.weight)
+            .sum();
+
+        let occupancy = total_weight as f64 / self.full_block_weight as f64;
+        self.update_multiplier(occupancy);
+
+        Ok(())
+    }
+
+    /// Scale `base_rate` by the current multiplier to produce the low/middle/high bands.
+    fn get_rate_estimates(&self) -> Result<FeeRateEstimate, EstimatorError> {
+        let multiplier = Self::get_multiplier(&self.db).map_err(|_| EstimatorError::NoEstimateAvailable)?;
+        let middle = self.base_rate * multiplier;
+
+        Ok(FeeRateEstimate {
+            high: middle * 1.5,
+            middle,
+            low: middle * 0.5,
+        })
     }
 }
 
@@ -296,14 +1100,10 @@ fn maybe_add_minimum_fee_rate(working_rates: &mut Vec<FeeRateAndWeight>, full_bl
         total_weight += rate_and_weight.weight;
     }
 
-    warn!(
-        "total_weight {} full_block_weight {}",
-        total_weight, full_block_weight
-    );
     if total_weight < full_block_weight {
         let weight_remaining = full_block_weight - total_weight;
         working_rates.push(FeeRateAndWeight {
-            fee_rate: 1f64,
+            fee_rate: FixedPoint::ONE,
             weight: weight_remaining,
         })
     }
@@ -316,22 +1116,16 @@ fn fee_rate_and_weight_from_receipt(
     block_limit: &ExecutionCost,
 ) -> Option<FeeRateAndWeight> {
     let (payload, fee, tx_size) = match tx_receipt.transaction {
-        TransactionOrigin::Stacks(ref tx) => {
-            let fee = tx.get_tx_fee();
-            warn!("fee_paid: {}", fee);
-            Some((&tx.payload, tx.get_tx_fee(), tx.tx_len()))
-        }
+        TransactionOrigin::Stacks(ref tx) => Some((&tx.payload, tx.get_tx_fee(), tx.tx_len())),
         TransactionOrigin::Burn(_) => None,
     }?;
     let scalar_cost = match payload {
         TransactionPayload::TokenTransfer(_, _, _) => {
             // TokenTransfers *only* contribute tx_len, and just have an empty ExecutionCost.
-            warn!("check");
             metric.from_len(tx_size)
         }
         TransactionPayload::Coinbase(_) => {
             // Coinbase txs are "free", so they don't factor into the fee market.
-            warn!("check");
             return None;
         }
         TransactionPayload::PoisonMicroblock(_, _)
@@ -339,30 +1133,220 @@ fn fee_rate_and_weight_from_receipt(
         | TransactionPayload::SmartContract(_) => {
             // These transaction payload types all "work" the same: they have associated ExecutionCosts
             // and contibute to the block length limit with their tx_len
-            warn!("check {:?}", &tx_receipt.execution_cost);
             metric.from_cost_and_len(&tx_receipt.execution_cost, &block_limit, tx_size)
         }
     };
-    warn!("scalar_cost {}", scalar_cost);
-    let denominator = if scalar_cost >= 1 {
-        scalar_cost as f64
+    let denominator = cmp::max(scalar_cost, 1);
+    let fee_rate = FixedPoint::from_ratio(fee, denominator);
+    let fee_rate = if fee_rate >= FixedPoint::ONE {
+        fee_rate
     } else {
-        1f64
+        FixedPoint::ONE
     };
-    let fee_rate = fee as f64 / denominator;
-    warn!("fee_rate {}", fee_rate);
-    let part1 = fee_rate >= 1f64;
-    let part2 = fee_rate.is_finite();
-    warn!("part1 {} part2 {}", part1, part2);
-    if fee_rate >= 1f64 && fee_rate.is_finite() {
-        Some(FeeRateAndWeight {
-            fee_rate,
-            weight: scalar_cost,
-        })
-    } else {
-        Some(FeeRateAndWeight {
-            fee_rate: 1f64,
-            weight: scalar_cost,
-        })
+    Some(FeeRateAndWeight {
+        fee_rate,
+        weight: scalar_cost,
+    })
+}
+
+/// Compute the proportion of `limit` that `numerator` represents, scaled into
+/// `PROPORTION_RESOLUTION` units (so that a dimension completely filling the block
+/// limit weighs `PROPORTION_RESOLUTION`). Saturates to `PROPORTION_RESOLUTION` if
+/// `limit` is 0, rather than dividing by zero.
+fn proportion_of_limit(numerator: u64, limit: u64) -> u64 {
+    if limit == 0 {
+        return PROPORTION_RESOLUTION;
+    }
+    cmp::min(
+        (numerator as u128 * PROPORTION_RESOLUTION as u128) / limit as u128,
+        u64::MAX as u128,
+    ) as u64
+}
+
+/// Compute an independent fee rate and weight for each `CostDimension` that this
+/// transaction contributes to, rather than flattening them into one scalar via
+/// `CostMetric`. Dimensions the transaction does not touch (e.g. the cost dimensions
+/// for a `TokenTransfer`) are omitted.
+fn fee_rate_and_weight_per_dimension(
+    tx_receipt: &StacksTransactionReceipt,
+    block_limit: &ExecutionCost,
+    block_length_limit: u64,
+) -> Option<Vec<(CostDimension, FeeRateAndWeight)>> {
+    let (payload, fee, tx_size) = match tx_receipt.transaction {
+        TransactionOrigin::Stacks(ref tx) => Some((&tx.payload, tx.get_tx_fee(), tx.tx_len())),
+        TransactionOrigin::Burn(_) => None,
+    }?;
+
+    let mut weight_by_dimension = Vec::new();
+    weight_by_dimension.push((
+        CostDimension::TxLen,
+        proportion_of_limit(tx_size, block_length_limit),
+    ));
+
+    match payload {
+        TransactionPayload::TokenTransfer(_, _, _) => {
+            // TokenTransfers only contribute `tx_len`; they have an empty ExecutionCost.
+        }
+        TransactionPayload::Coinbase(_) => {
+            // Coinbase txs are "free", so they don't factor into the fee market.
+            return None;
+        }
+        TransactionPayload::PoisonMicroblock(_, _)
+        | TransactionPayload::ContractCall(_)
+        | TransactionPayload::SmartContract(_) => {
+            let execution_cost = &tx_receipt.execution_cost;
+            weight_by_dimension.push((
+                CostDimension::RuntimeCost,
+                proportion_of_limit(execution_cost.runtime, block_limit.runtime),
+            ));
+            weight_by_dimension.push((
+                CostDimension::ReadCount,
+                proportion_of_limit(execution_cost.read_count, block_limit.read_count),
+            ));
+            weight_by_dimension.push((
+                CostDimension::ReadLength,
+                proportion_of_limit(execution_cost.read_length, block_limit.read_length),
+            ));
+            weight_by_dimension.push((
+                CostDimension::WriteCount,
+                proportion_of_limit(execution_cost.write_count, block_limit.write_count),
+            ));
+            weight_by_dimension.push((
+                CostDimension::WriteLength,
+                proportion_of_limit(execution_cost.write_length, block_limit.write_length),
+            ));
+        }
+    }
+
+    Some(
+        weight_by_dimension
+            .into_iter()
+            .filter(|(_, weight)| *weight > 0)
+            .map(|(dimension, weight)| {
+                let fee_rate = FixedPoint::from_ratio(fee, weight);
+                let fee_rate = if fee_rate >= FixedPoint::ONE {
+                    fee_rate
+                } else {
+                    FixedPoint::ONE
+                };
+                (dimension, FeeRateAndWeight { fee_rate, weight })
+            })
+            .collect(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fixed_point_from_ratio_rounds_towards_zero() {
+        assert_eq!(FixedPoint::from_ratio(1, 2).to_f64(), 0.5);
+        assert_eq!(FixedPoint::from_ratio(0, 5).to_f64(), 0.0);
+        assert_eq!(FixedPoint::from_ratio(10, 1).to_f64(), 10.0);
+        // (2 << 32) / 3 = 2863311530.67 in exact arithmetic
+        assert_eq!(FixedPoint::from_ratio(2, 3), FixedPoint(2863311530));
+    }
+
+    #[test]
+    fn fixed_point_from_ratio_saturates_on_zero_denominator() {
+        assert_eq!(FixedPoint::from_ratio(1, 0), FixedPoint(i128::MAX));
+    }
+
+    #[test]
+    fn fixed_point_checked_mul_and_div_round_trip() {
+        let a = FixedPoint::from_ratio(3, 2);
+        let b = FixedPoint::from_ratio(2, 1);
+        assert_eq!(a.checked_mul(b).to_f64(), 3.0);
+        assert_eq!(a.checked_div(b).to_f64(), 0.75);
+        assert_eq!(a.checked_mul(b).checked_div(b), a);
+    }
+
+    #[test]
+    fn fixed_point_checked_div_by_zero_saturates() {
+        let a = FixedPoint::from_ratio(1, 1);
+        assert_eq!(a.checked_div(FixedPoint(0)), FixedPoint(i128::MAX));
+    }
+
+    #[test]
+    fn fixed_point_to_storage_saturates_at_i64_bounds() {
+        let huge = FixedPoint(i128::from(i64::MAX) + 1);
+        assert_eq!(huge.to_storage(), i64::MAX);
+
+        let tiny = FixedPoint(i128::from(i64::MIN) - 1);
+        assert_eq!(tiny.to_storage(), i64::MIN);
+    }
+
+    #[test]
+    fn fixed_point_storage_round_trips_in_range() {
+        let original = FixedPoint::from_ratio(7, 4);
+        let restored = FixedPoint::from_storage(original.to_storage());
+        assert_eq!(original, restored);
+    }
+
+    fn rate_and_weight(fee_rate: u64, weight: u64) -> FeeRateAndWeight {
+        FeeRateAndWeight {
+            fee_rate: FixedPoint::from_ratio(fee_rate, 1),
+            weight,
+        }
+    }
+
+    #[test]
+    fn fee_rate_estimate_single_entry_returns_its_rate_for_all_bands() {
+        let sorted = vec![rate_and_weight(10, 100)];
+        let estimate = fee_rate_estimate_from_sorted_weighted_fees(&sorted);
+        assert_eq!(estimate.high, 10.0);
+        assert_eq!(estimate.middle, 10.0);
+        assert_eq!(estimate.low, 10.0);
+    }
+
+    #[test]
+    fn fee_rate_estimate_interpolates_middle_of_evenly_weighted_range() {
+        let sorted = vec![
+            rate_and_weight(1, 1),
+            rate_and_weight(2, 1),
+            rate_and_weight(3, 1),
+        ];
+        let estimate = fee_rate_estimate_from_sorted_weighted_fees(&sorted);
+        assert_eq!(estimate.middle, 2.0);
+        assert_eq!(estimate.low, 1.0);
+        assert_eq!(estimate.high, 3.0);
+    }
+
+    #[test]
+    fn fee_rate_estimate_clamps_to_first_and_last_rate_outside_observed_percentiles() {
+        // With only two evenly-weighted entries, the 5th and 95th percentile targets
+        //  fall outside the observed percentile range and should clamp to the first
+        //  and last rate rather than extrapolating past them.
+        let sorted = vec![rate_and_weight(10, 1), rate_and_weight(20, 1)];
+        let estimate = fee_rate_estimate_from_sorted_weighted_fees(&sorted);
+        assert_eq!(estimate.low, 10.0);
+        assert_eq!(estimate.high, 20.0);
+    }
+
+    #[test]
+    #[should_panic(expected = "cannot be empty")]
+    fn fee_rate_estimate_panics_on_empty_input() {
+        fee_rate_estimate_from_sorted_weighted_fees(&Vec::new());
+    }
+
+    #[test]
+    fn bounded_rollback_height_uses_fork_height_within_max_depth() {
+        assert_eq!(bounded_rollback_height(900, 1000), 900);
+    }
+
+    #[test]
+    fn bounded_rollback_height_clamps_to_maximum_rollback_depth() {
+        let max_observed_height = 5000;
+        let requested_fork_height = 0;
+        assert_eq!(
+            bounded_rollback_height(requested_fork_height, max_observed_height),
+            max_observed_height - MAXIMUM_ROLLBACK_DEPTH
+        );
+    }
+
+    #[test]
+    fn bounded_rollback_height_keeps_fork_height_above_max_observed_height() {
+        assert_eq!(bounded_rollback_height(10, 5), 10);
     }
 }
\ No newline at end of file